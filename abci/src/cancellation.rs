@@ -1,31 +1,104 @@
 use std::{
     marker::PhantomData,
     rc::Rc,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::Duration,
 };
 
-pub const CANCEL_PANIC_MSG: &'static str = "requested cancellation";
+pub const CANCEL_PANIC_MSG: &str = "requested cancellation";
 
-static FLAG: AtomicBool = AtomicBool::new(false);
+/// Shared state backing a single [`CancellationSource`] and all the
+/// [`CancellationToken`]s derived from it.
+#[derive(Debug, Default)]
+struct Shared {
+    cancelled: AtomicBool,
+    lock: Mutex<()>,
+    wake: Condvar,
+}
 
-#[derive(Debug, Copy, Clone, Default)]
-pub struct CancellationToken;
+impl Shared {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
 
-impl CancellationToken {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // Hold the lock while notifying so a waiter that is about to call
+        // `wait_timeout` can't miss this wakeup.
+        let _guard = self.lock.lock().unwrap();
+        self.wake.notify_all();
+    }
+
+    fn wait_timeout(&self, timeout: Duration) {
+        let guard = self.lock.lock().unwrap();
+        if self.is_cancelled() {
+            return;
+        }
+        let _ = self.wake.wait_timeout(guard, timeout);
+    }
+}
+
+/// Owns the cancellation state for a single server (or other unit of work).
+///
+/// Unlike a bare [`CancellationToken`], a `CancellationSource` is the only
+/// thing that can mint new tokens. Each source has its own independent
+/// `Arc`-backed state, so cancelling one source never affects tokens derived
+/// from a different source.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationSource {
+    shared: Arc<Shared>,
+}
+
+impl CancellationSource {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Mints a new [`CancellationToken`] tied to this source.
+    pub fn token(&self) -> CancellationToken {
+        CancellationToken {
+            shared: self.shared.clone(),
+        }
     }
 
     pub fn is_cancelled(&self) -> bool {
-        FLAG.load(Ordering::Relaxed)
+        self.shared.is_cancelled()
+    }
+
+    pub fn cancel(&self) {
+        self.shared.cancel()
     }
 
+    /// Blocks the calling thread until this source is cancelled or `timeout`
+    /// elapses, whichever happens first.
+    pub fn wait_timeout(&self, timeout: Duration) {
+        self.shared.wait_timeout(timeout)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    shared: Arc<Shared>,
+}
+
+impl CancellationToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.is_cancelled()
+    }
+
+    /// Panics with [`CANCEL_PANIC_MSG`] if and only if the owning
+    /// [`CancellationSource`] has actually been cancelled.
     pub fn panic_if_cancelled(&self) {
-        panic!("{CANCEL_PANIC_MSG}")
+        if self.is_cancelled() {
+            panic!("{CANCEL_PANIC_MSG}")
+        }
     }
 
     pub fn cancel(&self) {
-        FLAG.store(true, Ordering::Relaxed)
+        self.shared.cancel()
     }
 
     pub fn drop_guard(&self) -> TokenDropGuard {
@@ -71,3 +144,63 @@ impl Drop for TokenDropGuard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn independent_sources_do_not_affect_each_other() {
+        let a = CancellationSource::new();
+        let b = CancellationSource::new();
+        let token_a = a.token();
+        let token_b = b.token();
+
+        a.cancel();
+
+        assert!(a.is_cancelled());
+        assert!(token_a.is_cancelled());
+        assert!(!b.is_cancelled());
+        assert!(!token_b.is_cancelled());
+    }
+
+    #[test]
+    fn tokens_from_the_same_source_share_state() {
+        let source = CancellationSource::new();
+        let first = source.token();
+        let second = source.token();
+
+        first.cancel();
+
+        assert!(source.is_cancelled());
+        assert!(second.is_cancelled());
+    }
+
+    #[test]
+    fn panic_if_cancelled_is_a_noop_until_cancelled() {
+        let source = CancellationSource::new();
+        let token = source.token();
+
+        // Should not panic: nothing has been cancelled yet.
+        token.panic_if_cancelled();
+
+        source.cancel();
+        let result = std::panic::catch_unwind(move || token.panic_if_cancelled());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn wait_timeout_wakes_up_on_cancel() {
+        let source = CancellationSource::new();
+        let waiter = source.clone();
+        let handle = std::thread::spawn(move || {
+            waiter.wait_timeout(Duration::from_secs(5));
+            waiter.is_cancelled()
+        });
+
+        std::thread::sleep(Duration::from_millis(20));
+        source.cancel();
+
+        assert!(handle.join().unwrap());
+    }
+}
@@ -0,0 +1,253 @@
+//! Length-delimited framing for requests and responses exchanged with a
+//! single ABCI client connection.
+
+use std::{
+    io::{self, BufReader, Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{cancellation::CancellationToken, error::Error};
+
+/// Number of bytes used to encode a frame's big-endian length prefix.
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// The outcome of waiting for the next request frame.
+pub enum Frame {
+    /// A full request frame was read.
+    Request(Vec<u8>),
+    /// The client cleanly closed the connection between frames.
+    Closed,
+    /// No frame arrived before the connection's cancellation token was
+    /// cancelled.
+    Cancelled,
+}
+
+/// Reads length-delimited request frames from, and writes length-delimited
+/// response frames to, a single client connection.
+///
+/// A frame is a `u32` big-endian length prefix followed by that many bytes
+/// of payload. A declared request length greater than the configured
+/// `max_request_size` is rejected with [`Error::RequestTooLarge`] as soon as
+/// the length prefix is read, without ever allocating a buffer for the
+/// (unread) body.
+pub struct ServerCodec<S> {
+    reader: BufReader<S>,
+    max_request_size: Option<usize>,
+}
+
+impl<S: Read + Write> ServerCodec<S> {
+    /// Wraps `stream` in a codec that buffers reads in chunks of
+    /// `read_buf_size` bytes and rejects any request frame declaring a
+    /// length over `max_request_size` (if set).
+    pub fn new(stream: S, read_buf_size: usize, max_request_size: Option<usize>) -> Self {
+        Self {
+            reader: BufReader::with_capacity(read_buf_size, stream),
+            max_request_size,
+        }
+    }
+
+    /// Reads the next request frame.
+    ///
+    /// The underlying connection is expected to have a short, internal read
+    /// deadline set (see `server::READ_POLL_INTERVAL`) so that a timed-out
+    /// read here doesn't mean an error: it's treated as a chance to
+    /// re-check `token` and, if `stall_timeout` is set, how long it's been
+    /// since any bytes of a new frame arrived. If that exceeds
+    /// `stall_timeout`, the connection is treated as stalled and closed,
+    /// matching the documented "close on stall" semantics of
+    /// [`crate::server::ConnectionConfig::read_timeout`].
+    pub fn next(
+        &mut self,
+        token: &CancellationToken,
+        stall_timeout: Option<Duration>,
+    ) -> Result<Frame, Error> {
+        let mut len_buf = [0u8; LENGTH_PREFIX_SIZE];
+        match self.read_polling(&mut len_buf, token, stall_timeout)? {
+            ReadOutcome::Complete => {},
+            ReadOutcome::Eof => return Ok(Frame::Closed),
+            ReadOutcome::Cancelled => return Ok(Frame::Cancelled),
+        }
+        let declared_len = u32::from_be_bytes(len_buf) as usize;
+
+        if let Some(max) = self.max_request_size {
+            if declared_len > max {
+                return Err(Error::request_too_large(declared_len, max));
+            }
+        }
+
+        let mut body = vec![0u8; declared_len];
+        match self.read_polling(&mut body, token, stall_timeout)? {
+            ReadOutcome::Complete => Ok(Frame::Request(body)),
+            ReadOutcome::Eof => Err(Error::io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed mid-frame",
+            ))),
+            ReadOutcome::Cancelled => Ok(Frame::Cancelled),
+        }
+    }
+
+    /// Fills `buf` completely, retrying across the internal read-poll
+    /// timeout so `token` and `stall_timeout` can be re-checked between
+    /// kernel-level reads, without ever discarding bytes already read into
+    /// `buf`.
+    fn read_polling(
+        &mut self,
+        buf: &mut [u8],
+        token: &CancellationToken,
+        stall_timeout: Option<Duration>,
+    ) -> Result<ReadOutcome, Error> {
+        let mut filled = 0;
+        let mut idle_since = Instant::now();
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(ReadOutcome::Eof),
+                Ok(0) => {
+                    return Err(Error::io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "connection closed mid-frame",
+                    )));
+                },
+                Ok(n) => {
+                    filled += n;
+                    idle_since = Instant::now();
+                },
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    if token.is_cancelled() {
+                        return Ok(ReadOutcome::Cancelled);
+                    }
+                    if let Some(stall) = stall_timeout {
+                        if idle_since.elapsed() >= stall {
+                            return Err(Error::io(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "no data received within the configured read timeout",
+                            )));
+                        }
+                    }
+                },
+                Err(e) => return Err(Error::io(e)),
+            }
+        }
+        Ok(ReadOutcome::Complete)
+    }
+
+    /// Writes a response frame, flushing it to the underlying connection.
+    pub fn send(&mut self, response: Vec<u8>) -> Result<(), Error> {
+        let len = u32::try_from(response.len())
+            .map_err(|_| Error::response_too_large(response.len(), u32::MAX as usize))?;
+        let stream = self.reader.get_mut();
+        stream.write_all(&len.to_be_bytes()).map_err(Error::io)?;
+        stream.write_all(&response).map_err(Error::io)?;
+        stream.flush().map_err(Error::io)
+    }
+}
+
+/// The result of trying to fill a fixed-size buffer from the connection.
+enum ReadOutcome {
+    Complete,
+    Eof,
+    Cancelled,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::cancellation::CancellationSource;
+
+    /// An in-memory stream backed by a shared buffer, so `send` can be
+    /// observed after the codec has written to it.
+    #[derive(Clone, Default)]
+    struct MemoryStream(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+    impl Read for MemoryStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().read(buf)
+        }
+    }
+
+    impl Write for MemoryStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.lock().unwrap().flush()
+        }
+    }
+
+    fn codec_with_bytes(bytes: Vec<u8>, max_request_size: Option<usize>) -> ServerCodec<MemoryStream> {
+        let stream = MemoryStream(Arc::new(Mutex::new(Cursor::new(bytes))));
+        ServerCodec::new(stream, 1024, max_request_size)
+    }
+
+    #[test]
+    fn rejects_oversized_declared_length_without_reading_body() {
+        // Declares a 100-byte body but only actually supplies 1 byte: if the
+        // codec tried to read the body it would block/EOF rather than
+        // rejecting up front.
+        let mut frame = 100u32.to_be_bytes().to_vec();
+        frame.push(0xAB);
+        let mut codec = codec_with_bytes(frame, Some(10));
+
+        let source = CancellationSource::new();
+        let token = source.token();
+        let err = codec
+            .next(&token, None)
+            .err()
+            .expect("oversized frame should be rejected");
+        assert!(matches!(
+            err,
+            Error::RequestTooLarge {
+                declared_len: 100,
+                max: 10
+            }
+        ));
+    }
+
+    #[test]
+    fn reads_a_request_within_the_limit() {
+        let mut frame = 3u32.to_be_bytes().to_vec();
+        frame.extend_from_slice(b"abc");
+        let mut codec = codec_with_bytes(frame, Some(10));
+
+        let source = CancellationSource::new();
+        let token = source.token();
+        match codec.next(&token, None).unwrap() {
+            Frame::Request(body) => assert_eq!(body, b"abc"),
+            _ => panic!("expected a request frame"),
+        }
+    }
+
+    #[test]
+    fn reports_clean_close_between_frames() {
+        let mut codec = codec_with_bytes(Vec::new(), None);
+        let source = CancellationSource::new();
+        let token = source.token();
+        assert!(matches!(codec.next(&token, None).unwrap(), Frame::Closed));
+    }
+
+    #[test]
+    fn oversized_response_error_is_distinct_and_small_responses_send() {
+        let stream = MemoryStream::default();
+        let mut codec = ServerCodec::new(stream, 1024, None);
+
+        // We can't actually allocate a `u32::MAX + 1`-byte `Vec` in a test,
+        // so check that such a length fails `u32::try_from` and inspect the
+        // error `send` builds for it directly.
+        let oversized_len = u32::MAX as usize + 1;
+        assert!(u32::try_from(oversized_len).is_err());
+        let err = Error::response_too_large(oversized_len, u32::MAX as usize);
+        assert!(matches!(err, Error::ResponseTooLarge { .. }));
+        assert!(!err.to_string().contains("request"));
+
+        // A response that does fit still sends successfully.
+        codec.send(vec![1, 2, 3]).unwrap();
+    }
+}
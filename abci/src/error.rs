@@ -0,0 +1,57 @@
+//! Error types for the ABCI server.
+
+use std::{fmt, io};
+
+/// Errors that can occur during operation of the ABCI server.
+#[derive(Debug)]
+pub enum Error {
+    /// An underlying I/O error occurred (including a read or write timing
+    /// out on a connection configured with [`crate::server::ConnectionConfig`]).
+    Io(io::Error),
+    /// A client's framed request declared a length greater than the
+    /// server's configured `max_request_size`. The frame is rejected as
+    /// soon as the length prefix is read, before its body is buffered.
+    RequestTooLarge { declared_len: usize, max: usize },
+    /// An application's response does not fit in the codec's `u32`
+    /// length-prefixed frame format.
+    ResponseTooLarge { len: usize, max: usize },
+}
+
+impl Error {
+    pub fn io(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+
+    pub fn request_too_large(declared_len: usize, max: usize) -> Self {
+        Self::RequestTooLarge { declared_len, max }
+    }
+
+    pub fn response_too_large(len: usize, max: usize) -> Self {
+        Self::ResponseTooLarge { len, max }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::RequestTooLarge { declared_len, max } => write!(
+                f,
+                "request of {declared_len} bytes exceeds the maximum allowed size of {max} bytes"
+            ),
+            Self::ResponseTooLarge { len, max } => write!(
+                f,
+                "response of {len} bytes exceeds the maximum encodable size of {max} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::RequestTooLarge { .. } | Self::ResponseTooLarge { .. } => None,
+        }
+    }
+}
@@ -1,22 +1,158 @@
 //! ABCI application server interface.
 
 use std::{
+    io::{self, Read, Write},
     net::{TcpListener, TcpStream, ToSocketAddrs},
-    thread,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
 };
 
-use gancellation_token::{CancellationSource, CancellationToken};
 use tracing::{error, info};
 
-use crate::{application::RequestDispatcher, codec::ServerCodec, error::Error, Application};
+use crate::{
+    application::RequestDispatcher,
+    cancellation::{CancellationSource, CancellationToken},
+    codec::{Frame, ServerCodec},
+    error::Error,
+    Application,
+};
 
 /// The size of the read buffer for each incoming connection to the ABCI
 /// server (1MB).
 pub const DEFAULT_SERVER_READ_BUF_SIZE: usize = 1024 * 1024;
 
+/// How long the main thread waits on the cancellation source between checks,
+/// in case cancellation happens to race with a missed wakeup.
+const SHUTDOWN_WAIT_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// How long the accept loop sleeps between polls of a non-blocking listener
+/// that has no pending connection, bounding how quickly it notices
+/// cancellation while idle.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a connection's read is allowed to block before it is
+/// interrupted to re-check the cancellation token. This is an internal
+/// implementation detail, independent of the user-configurable
+/// [`ConnectionConfig::read_timeout`], and exists solely so a connection
+/// that is open but idle (no request pending) still notices cancellation
+/// instead of blocking forever. `read_timeout` itself is enforced in
+/// [`crate::codec::ServerCodec`] on top of this polling.
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Abstracts over the concrete transport a [`Server`] accepts connections on,
+/// so the same request-handling logic can run over TCP or a Unix domain
+/// socket.
+pub trait Listener: Send + 'static {
+    /// The per-connection stream type produced by this listener.
+    type Connection: Read + Write + ConnectionTimeouts + Send + 'static;
+
+    /// Blocks until the next incoming connection arrives, returning it along
+    /// with a human-readable string identifying the peer. When the listener
+    /// is in non-blocking mode (see [`Listener::set_nonblocking`]), returns
+    /// an `io::Error` of kind [`io::ErrorKind::WouldBlock`] if no connection
+    /// is currently available.
+    fn accept(&self) -> io::Result<(Self::Connection, String)>;
+
+    /// Puts the listener into or out of non-blocking mode, so the accept
+    /// loop can poll it instead of parking forever inside `accept()` with no
+    /// way to notice that cancellation has been requested.
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+
+    /// Called once [`Server::listen`] has stopped accepting new connections,
+    /// to release any resources owned by the listener (e.g. a socket file).
+    fn cleanup(&self) {}
+}
+
+/// Connections that support OS-level read/write deadlines, so [`Server`] can
+/// bound how long a `handle_client` thread will block on a slow or stalled
+/// peer, regardless of the underlying transport.
+pub trait ConnectionTimeouts {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl ConnectionTimeouts for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+}
+
+impl ConnectionTimeouts for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_write_timeout(self, timeout)
+    }
+}
+
+impl Listener for TcpListener {
+    type Connection = TcpStream;
+
+    fn accept(&self) -> io::Result<(Self::Connection, String)> {
+        let (stream, addr) = TcpListener::accept(self)?;
+        Ok((stream, addr.to_string()))
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpListener::set_nonblocking(self, nonblocking)
+    }
+}
+
+/// A Unix domain socket listener, paired with the path it is bound to so
+/// that the socket file can be cleaned up on shutdown.
+pub struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Listener for UnixSocketListener {
+    type Connection = UnixStream;
+
+    fn accept(&self) -> io::Result<(Self::Connection, String)> {
+        let (stream, addr) = self.listener.accept()?;
+        let peer = addr
+            .as_pathname()
+            .map(|p| format!("unix:{}", p.display()))
+            .unwrap_or_else(|| "unix:<unnamed>".to_string());
+        Ok((stream, peer))
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.listener.set_nonblocking(nonblocking)
+    }
+
+    fn cleanup(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Per-connection limits applied by [`Server`]: how long a connection may sit
+/// idle waiting on a new request before it's dropped, how long a write may
+/// block, and the largest framed request it will allow a client to send.
+///
+/// All limits default to `None` (unbounded), matching the server's prior,
+/// unbounded behaviour.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionConfig {
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub max_request_size: Option<usize>,
+}
+
 /// Allows us to configure and construct an ABCI server.
 pub struct ServerBuilder {
     read_buf_size: usize,
+    max_connections: Option<usize>,
+    connection_config: ConnectionConfig,
 }
 
 impl ServerBuilder {
@@ -26,29 +162,97 @@ impl ServerBuilder {
     /// incoming data from the client. This needs to be tuned for your
     /// application.
     pub fn new(read_buf_size: usize) -> Self {
-        Self { read_buf_size }
+        Self {
+            read_buf_size,
+            max_connections: None,
+            connection_config: ConnectionConfig::default(),
+        }
     }
 
-    /// Constructor for an ABCI server.
+    /// Caps the number of simultaneously live client connections this
+    /// server will handle.
+    ///
+    /// Once the cap is reached, the accept loop pauses (it stops calling
+    /// [`Listener::accept`]) until the live count drops slightly below the
+    /// cap again, to avoid thrashing. Defaults to unbounded.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Bounds how long a connection may sit idle waiting for the next
+    /// request before it's closed. Defaults to no timeout, so a stalled peer
+    /// can otherwise pin a handler thread indefinitely.
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.connection_config.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Bounds how long a connection may block waiting to write a response
+    /// before it's closed. Defaults to no timeout.
+    pub fn write_timeout(mut self, write_timeout: Duration) -> Self {
+        self.connection_config.write_timeout = Some(write_timeout);
+        self
+    }
+
+    /// Caps the size, in bytes, of a single framed request the server will
+    /// accept from a client. Requests declaring a larger length are rejected
+    /// by [`ServerCodec`] without being buffered. Defaults to unbounded.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.connection_config.max_request_size = Some(max_request_size);
+        self
+    }
+
+    /// Constructor for a TCP-based ABCI server.
     ///
     /// Binds the server to the given address. You must subsequently call the
     /// [`Server::listen`] method in order for incoming connections' requests
     /// to be routed to the specified ABCI application.
-    pub fn bind<Addr, App>(self, addr: Addr, app: App) -> Result<Server<App>, Error>
+    pub fn bind<Addr, App>(self, addr: Addr, app: App) -> Result<Server<App, TcpListener>, Error>
     where
         Addr: ToSocketAddrs,
         App: Application,
     {
         let listener = TcpListener::bind(addr).map_err(Error::io)?;
         let local_addr = listener.local_addr().map_err(Error::io)?.to_string();
+        // Non-blocking so the accept loop in `Server::listen` can poll it
+        // against the cancellation token instead of parking in `accept()`
+        // with no way to notice a shutdown request.
+        listener.set_nonblocking(true).map_err(Error::io)?;
         info!("ABCI server running at {}", local_addr);
-        Ok(Server {
+        Ok(Server::new(
             app,
             listener,
             local_addr,
-            read_buf_size: self.read_buf_size,
-            cancellation_source: CancellationSource::new(),
-        })
+            self.read_buf_size,
+            self.max_connections,
+            self.connection_config,
+        ))
+    }
+
+    /// Constructor for a Unix-domain-socket-based ABCI server.
+    ///
+    /// Binds the server to the given path. This is the lower-latency option
+    /// for consensus engine and application processes co-located on the same
+    /// host. The socket file is removed once the server shuts down.
+    pub fn bind_unix<P, App>(self, path: P, app: App) -> Result<Server<App, UnixSocketListener>, Error>
+    where
+        P: AsRef<Path>,
+        App: Application,
+    {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path).map_err(Error::io)?;
+        listener.set_nonblocking(true).map_err(Error::io)?;
+        let local_addr = format!("unix:{}", path.display());
+        info!("ABCI server running at {}", local_addr);
+        Ok(Server::new(
+            app,
+            UnixSocketListener { listener, path },
+            local_addr,
+            self.read_buf_size,
+            self.max_connections,
+            self.connection_config,
+        ))
     }
 }
 
@@ -56,59 +260,192 @@ impl Default for ServerBuilder {
     fn default() -> Self {
         Self {
             read_buf_size: DEFAULT_SERVER_READ_BUF_SIZE,
+            max_connections: None,
+            connection_config: ConnectionConfig::default(),
         }
     }
 }
 
-/// A TCP-based server for serving a specific ABCI application.
+/// Tracks the number of live `handle_client` threads and implements the
+/// accept loop's backpressure: accepting pauses once `max` connections are
+/// live, and resumes once the count falls to `low_watermark`, so the accept
+/// loop doesn't thrash right at the cap.
+struct ConnectionGate {
+    count: Mutex<usize>,
+    cond: Condvar,
+    max: usize,
+    low_watermark: usize,
+}
+
+impl ConnectionGate {
+    fn new(max: usize) -> Self {
+        let low_watermark = max.saturating_sub((max / 10).max(1));
+        Self {
+            count: Mutex::new(0),
+            cond: Condvar::new(),
+            max,
+            low_watermark,
+        }
+    }
+
+    /// Returns at once while the live-connection count is below the cap;
+    /// once the cap is reached, blocks until the count falls to
+    /// `low_watermark`, re-checking `token` periodically so shutdown isn't
+    /// delayed by a full connection table.
+    fn wait_for_capacity(&self, token: &CancellationToken) {
+        let mut count = self.count.lock().unwrap();
+        if *count < self.max {
+            return;
+        }
+        while *count > self.low_watermark && !token.is_cancelled() {
+            let (guard, _) = self
+                .cond
+                .wait_timeout(count, Duration::from_millis(100))
+                .unwrap();
+            count = guard;
+        }
+    }
+
+    fn acquire(self: &Arc<Self>) -> ConnectionGuard {
+        *self.count.lock().unwrap() += 1;
+        ConnectionGuard { gate: self.clone() }
+    }
+
+    fn release(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        if *count <= self.low_watermark {
+            self.cond.notify_all();
+        }
+    }
+
+    fn live(&self) -> usize {
+        *self.count.lock().unwrap()
+    }
+}
+
+/// Released when a connection's `handle_client` thread finishes, freeing up
+/// a slot in the owning [`ConnectionGate`].
+struct ConnectionGuard {
+    gate: Arc<ConnectionGate>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.gate.release();
+    }
+}
+
+/// A server for serving a specific ABCI application over some [`Listener`]
+/// transport (TCP or a Unix domain socket).
 ///
 /// Each incoming connection is handled in a separate thread. The ABCI
 /// application is cloned for access in each thread. It is up to the
 /// application developer to manage shared state across these different
 /// threads.
-pub struct Server<App> {
+pub struct Server<App, L> {
     app: App,
-    listener: TcpListener,
+    listener: L,
     local_addr: String,
     read_buf_size: usize,
     cancellation_source: CancellationSource,
+    /// Join handles of currently-live `handle_client` threads, so that
+    /// [`Server::listen`] can wait for in-flight connections to drain on
+    /// shutdown instead of dropping them mid-response.
+    handlers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    connection_gate: Arc<ConnectionGate>,
+    connection_config: ConnectionConfig,
 }
 
-impl<App: Application> Server<App> {
+impl<App: Application, L: Listener> Server<App, L> {
+    fn new(
+        app: App,
+        listener: L,
+        local_addr: String,
+        read_buf_size: usize,
+        max_connections: Option<usize>,
+        connection_config: ConnectionConfig,
+    ) -> Self {
+        Self {
+            app,
+            listener,
+            local_addr,
+            read_buf_size,
+            cancellation_source: CancellationSource::new(),
+            handlers: Arc::new(Mutex::new(Vec::new())),
+            connection_config,
+            connection_gate: Arc::new(ConnectionGate::new(max_connections.unwrap_or(usize::MAX))),
+        }
+    }
+
     pub fn token(&mut self) -> CancellationToken {
         self.cancellation_source.token()
     }
 
+    /// The number of client connections currently being handled.
+    pub fn live_connections(&self) -> usize {
+        self.connection_gate.live()
+    }
+
     /// Initiate a blocking listener for incoming connections.
+    ///
+    /// Once the server's [`CancellationToken`] is cancelled, the accept loop
+    /// stops taking new connections, and this method blocks until every
+    /// in-flight `handle_client` thread has finished sending the response it
+    /// was working on and has dropped its connection.
     pub fn listen(mut self) -> Result<(), Error> {
         let mut token = self.cancellation_source.token();
+        let handlers = self.handlers.clone();
+        let connection_gate = self.connection_gate.clone();
+        let connection_config = self.connection_config;
 
-        let _ = thread::spawn(move || {
+        let accept_thread = thread::spawn(move || {
             while !token.is_cancelled() {
-                let connection = self.listener.accept().map_err(Error::io);
+                connection_gate.wait_for_capacity(&token);
+                if token.is_cancelled() {
+                    break;
+                }
 
-                match connection {
+                // The listener is non-blocking (see `ServerBuilder::bind`),
+                // so a `WouldBlock` just means no connection is pending yet;
+                // sleep briefly and re-check the token instead of parking in
+                // `accept()` with no way to notice cancellation.
+                match self.listener.accept() {
                     Ok((stream, addr)) => {
-                        let addr = addr.to_string();
                         info!("Incoming connection from: {}", addr);
+                        let guard = connection_gate.acquire();
                         Self::spawn_client_handler(
+                            &handlers,
                             stream,
                             addr,
                             self.app.clone(),
                             self.read_buf_size,
+                            connection_config,
                             token.clone(),
+                            guard,
                         );
                     },
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    },
                     Err(err) => {
                         error!("Error receiving connection: {err}");
                         token.cancel();
                     },
                 }
             }
+            self.listener.cleanup();
         });
 
         while !self.cancellation_source.is_cancelled() {
-            std::thread::sleep(std::time::Duration::from_millis(100))
+            self.cancellation_source.wait_timeout(SHUTDOWN_WAIT_TIMEOUT);
+        }
+
+        // Stop accepting new connections, then let every connection that was
+        // already in flight finish sending its current response.
+        let _ = accept_thread.join();
+        for handler in self.handlers.lock().unwrap().drain(..) {
+            let _ = handler.join();
         }
 
         Ok(())
@@ -119,47 +456,132 @@ impl<App: Application> Server<App> {
         self.local_addr.clone()
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn spawn_client_handler(
-        stream: TcpStream,
+        handlers: &Arc<Mutex<Vec<JoinHandle<()>>>>,
+        stream: L::Connection,
         addr: String,
         app: App,
         read_buf_size: usize,
+        connection_config: ConnectionConfig,
         token: CancellationToken,
+        guard: ConnectionGuard,
     ) {
-        let _ = thread::spawn(move || Self::handle_client(stream, addr, app, read_buf_size, token));
+        let handle = thread::spawn(move || {
+            Self::handle_client(stream, addr, app, read_buf_size, connection_config, token);
+            // Held for the lifetime of the handler thread; dropping it here
+            // frees up a slot in the connection gate.
+            drop(guard);
+        });
+        let mut handlers = handlers.lock().unwrap();
+        // Prune threads that have already finished instead of only draining
+        // the list once, at shutdown, which would otherwise let it grow
+        // without bound over the life of a long-running server.
+        handlers.retain(|handle| !handle.is_finished());
+        handlers.push(handle);
     }
 
     fn handle_client(
-        stream: TcpStream,
+        stream: L::Connection,
         addr: String,
         app: App,
         read_buf_size: usize,
+        connection_config: ConnectionConfig,
         token: CancellationToken,
     ) {
-        let mut codec = ServerCodec::new(stream, read_buf_size);
+        // The socket-level read deadline is always the short internal poll
+        // interval, never the user-configured `read_timeout` directly: that
+        // would leave an idle connection blocked for the full duration (or
+        // forever, if unset) instead of periodically re-checking `token`.
+        // `read_timeout` itself is enforced by `ServerCodec::next`, which
+        // tracks how long it's been since any bytes of a new frame arrived.
+        if let Err(e) = stream.set_read_timeout(Some(READ_POLL_INTERVAL)) {
+            error!("Failed to set read timeout for client {}: {:?}", addr, e);
+            return;
+        }
+        if let Err(e) = stream.set_write_timeout(connection_config.write_timeout) {
+            error!("Failed to set write timeout for client {}: {:?}", addr, e);
+            return;
+        }
+
+        let mut codec = ServerCodec::new(stream, read_buf_size, connection_config.max_request_size);
         info!("Listening for incoming requests from {}", addr);
         while !token.is_cancelled() {
-            let request = match codec.next() {
-                Some(result) => match result {
-                    Ok(r) => r,
-                    Err(e) => {
-                        error!(
-                            "Failed to read incoming request from client {}: {:?}",
-                            addr, e
-                        );
-                        return;
-                    },
-                },
-                None => {
+            let request = match codec.next(&token, connection_config.read_timeout) {
+                Ok(Frame::Request(r)) => r,
+                Ok(Frame::Closed) => {
                     info!("Client {} terminated stream", addr);
                     return;
                 },
+                Ok(Frame::Cancelled) => break,
+                Err(e) => {
+                    // Covers a malformed/oversized length prefix (rejected
+                    // by the codec without buffering it) and a read timing
+                    // out on a stalled peer; either way we just log and
+                    // close the connection.
+                    error!(
+                        "Failed to read incoming request from client {}: {:?}",
+                        addr, e
+                    );
+                    return;
+                },
             };
+            // A request is already in flight: finish handling it and sending
+            // the response even if cancellation was requested while we were
+            // waiting for it, then drop the connection on the next check.
             let response = app.handle(request, &token);
             if let Err(e) = codec.send(response) {
                 error!("Failed sending response to client {}: {:?}", addr, e);
                 return;
             }
         }
+        info!("Shutting down connection to {} after cancellation", addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gate_blocks_until_capacity_frees() {
+        let gate = Arc::new(ConnectionGate::new(1));
+        let guard = gate.acquire();
+        assert_eq!(gate.live(), 1);
+
+        let waiter_gate = gate.clone();
+        let source = CancellationSource::new();
+        let token = source.token();
+        let handle = thread::spawn(move || {
+            waiter_gate.wait_for_capacity(&token);
+        });
+
+        // Give the waiter a chance to observe the full gate before freeing
+        // the slot.
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+
+        handle.join().unwrap();
+        assert_eq!(gate.live(), 0);
+    }
+
+    #[test]
+    fn low_watermark_is_below_the_cap() {
+        assert_eq!(ConnectionGate::new(10).low_watermark, 9);
+        assert_eq!(ConnectionGate::new(100).low_watermark, 90);
+        assert_eq!(ConnectionGate::new(1).low_watermark, 0);
+    }
+
+    #[test]
+    fn wait_for_capacity_returns_promptly_once_cancelled() {
+        let gate = ConnectionGate::new(1);
+        let _held = gate.acquire();
+        let source = CancellationSource::new();
+        let token = source.token();
+        source.cancel();
+
+        // Should return even though the gate is still full, since the token
+        // is already cancelled.
+        gate.wait_for_capacity(&token);
     }
 }